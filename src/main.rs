@@ -1,10 +1,12 @@
 #![doc = include_str!("../README.md")]
 use anyhow::*;
 use clap::Parser;
+use serde::{Deserialize, Serialize};
+use sodiumoxide::crypto::secretstream::xchacha20poly1305 as secretstream;
 use sodiumoxide::crypto::{pwhash, secretbox};
 use std::{
     borrow::Cow,
-    io::{Read, Write},
+    io::{BufReader, Cursor, Read, Write},
     path::{Path, PathBuf},
 };
 
@@ -15,6 +17,22 @@ struct Opt {
     #[clap(long, global = true)]
     file: Option<PathBuf>,
 
+    /// S3 bucket to store the backup in instead of a local file
+    #[clap(long, global = true)]
+    s3_bucket: Option<String>,
+
+    /// Object key within the S3 bucket (defaults to backup.json.enc)
+    #[clap(long, global = true)]
+    s3_key: Option<String>,
+
+    /// S3 region name (e.g. us-east-1)
+    #[clap(long, global = true)]
+    s3_region: Option<String>,
+
+    /// S3-compatible endpoint URL, for non-AWS providers
+    #[clap(long, global = true)]
+    s3_endpoint: Option<String>,
+
     /// Verbose output?
     #[clap(long, short, global = true)]
     verbose: bool,
@@ -29,32 +47,388 @@ enum Command {
     ///
     /// Note that you will likely need to `bw login` first to provide MFA information.
     Backup {
-        /// Email address for account to back up
+        /// Email address for account to back up (defaults to the configured value)
+        #[clap(long)]
+        email: Option<String>,
+
+        /// KDF operations limit (defaults to the configured value, then
+        /// libsodium's interactive value)
+        #[clap(long)]
+        ops_limit: Option<u64>,
+
+        /// KDF memory limit in bytes (defaults to the configured value, then
+        /// libsodium's interactive value)
         #[clap(long)]
-        email: String,
+        mem_limit: Option<u64>,
+
+        /// Export format passed through to `bw export`
+        #[clap(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
     },
     /// Decrypt a previous captured backup file.
-    Restore {},
+    Restore {
+        /// Re-encode the decrypted BitWarden JSON into this format instead of
+        /// emitting the raw stored bytes
+        #[clap(long, value_enum)]
+        to: Option<RestoreFormat>,
+    },
+    /// Check that a backup decrypts and contains well-formed data, printing
+    /// only a summary and never the secrets themselves.
+    Verify {},
+    /// Show or update the persisted configuration.
+    Config {
+        #[clap(subcommand)]
+        cmd: ConfigCommand,
+    },
+}
+
+#[derive(clap::Parser)]
+enum ConfigCommand {
+    /// Print the current configuration as JSON.
+    Show {},
+    /// Set a configuration value. Valid keys: email, file, ops_limit, mem_limit.
+    Set {
+        /// Configuration key to update
+        key: String,
+        /// New value
+        value: String,
+    },
+}
+
+/// Export formats understood by `bw export`.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ExportFormat {
+    Json,
+    Csv,
+    EncryptedJson,
+}
+
+impl ExportFormat {
+    /// The literal value `bw export --format` expects.
+    fn as_bw_arg(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::EncryptedJson => "encrypted_json",
+        }
+    }
+}
+
+/// Formats `restore` can re-encode the decrypted payload into.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum RestoreFormat {
+    Json,
+    Csv,
+}
+
+/// Persisted settings, stored as JSON at the location given by
+/// [`Config::path`], so the tool can run from cron without repeating the email
+/// and destination.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ops_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mem_limit: Option<u64>,
+}
+
+impl Config {
+    /// Location of the config file. When an explicit `--file` destination is
+    /// given the config lives next to it; otherwise it falls back to the
+    /// project configuration directory. `dest` is the raw `--file` flag, so
+    /// resolving it here does not depend on the config we are about to load.
+    fn path(dest: Option<&Path>) -> Result<PathBuf> {
+        // A bare file name has an empty parent: that is the current directory.
+        let dest_dir = dest
+            .and_then(Path::parent)
+            .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p });
+        let mut path = match dest_dir {
+            Some(parent) => parent.to_owned(),
+            None => directories_next::ProjectDirs::from("com", "Snoyman", "BitWarden Backup")
+                .context("Unable to get project directories")
+                .map(|pd| pd.config_dir().to_owned())?,
+        };
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Could not create directory {}", path.display()))?;
+        path.push("config.json");
+        Ok(path)
+    }
+
+    /// Load the config, returning the default if no file exists yet.
+    fn load(dest: Option<&Path>) -> Result<Config> {
+        let path = Config::path(dest)?;
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Could not parse config at {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    /// Persist the config back to disk.
+    fn save(&self, dest: Option<&Path>) -> Result<()> {
+        let path = Config::path(dest)?;
+        let json = serde_json::to_vec_pretty(self).context("Could not serialize config")?;
+        std::fs::write(&path, json).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "email" => self.email = Some(value.to_owned()),
+            "file" => self.file = Some(PathBuf::from(value)),
+            "ops_limit" => {
+                self.ops_limit = Some(value.parse().context("ops_limit must be an integer")?)
+            }
+            "mem_limit" => {
+                self.mem_limit = Some(value.parse().context("mem_limit must be an integer")?)
+            }
+            other => bail!("Unknown config key: {other}"),
+        }
+        Ok(())
+    }
 }
 
 const PASSWORDENV: &str = "BW_PASSWORD";
 const SESSIONENV: &str = "BW_SESSION";
 
+/// Magic bytes prefixing a versioned backup file.
+const MAGIC: &[u8; 4] = b"BWB1";
+/// Current header version written by `seal`.
+const VERSION: u8 = 2;
+/// Size of the legacy v1 header: magic, version, the two KDF limits, then the
+/// salt and the secretbox nonce.
+const V1_HEADER_LEN: usize = MAGIC.len() + 1 + 8 + 8 + pwhash::SALTBYTES + secretbox::NONCEBYTES;
+/// Plaintext chunk size fed to the secretstream, in bytes.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Derive a secret key from the master password using the given work factor.
+fn derive_key(
+    password: &str,
+    salt: &pwhash::Salt,
+    ops: pwhash::OpsLimit,
+    mem: pwhash::MemLimit,
+) -> Result<secretstream::Key> {
+    let mut kb = [0; secretstream::KEYBYTES];
+    pwhash::derive_key(&mut kb, password.as_bytes(), salt, ops, mem)
+        .ok()
+        .context("Could not derive key")?;
+    Ok(secretstream::Key(kb))
+}
+
+/// A destination that a sealed backup can be streamed to and read back from.
+/// The local filesystem is the default; an S3-compatible object store lets
+/// backups live off-machine.
+///
+/// `put` hands back a [`BackupSink`] and `get` a reader so the sealed stream
+/// flows straight through without ever being held whole in memory. The
+/// filesystem backend honours that end to end; the S3 backend has to buffer
+/// the full object in RAM on both paths, because the object-store API only
+/// speaks in byte slices. So only the local path delivers chunk0-2's bounded
+/// memory, and off-machine backends accept the full-buffer tradeoff.
+trait BackupStore {
+    /// Open a sink for the sealed bytes, replacing any previous contents once
+    /// [`BackupSink::finish`] is called.
+    fn put(&self) -> Result<Box<dyn BackupSink>>;
+    /// Open the sealed bytes for reading.
+    fn get(&self) -> Result<Box<dyn Read>>;
+    /// Human-readable location, used for the "saved to" message.
+    fn describe(&self) -> String;
+}
+
+/// A streaming sink for a sealed backup. Bytes are written incrementally and
+/// only committed to the destination by `finish`; dropping a sink without
+/// calling `finish` discards the write.
+trait BackupSink: Write {
+    /// Commit the written bytes to the destination.
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Sealed backup stored as a single file on the local filesystem.
+struct LocalStore {
+    path: PathBuf,
+}
+
+impl BackupStore for LocalStore {
+    fn put(&self) -> Result<Box<dyn BackupSink>> {
+        // Stream into a sibling temp file and rename it into place on `finish`,
+        // so a failed backup never truncates the previous file.
+        let tmp = self.path.with_extension("tmp");
+        let file = std::fs::File::create(&tmp).context("Could not open save file")?;
+        Ok(Box::new(LocalSink {
+            path: self.path.clone(),
+            tmp,
+            file,
+        }))
+    }
+
+    fn get(&self) -> Result<Box<dyn Read>> {
+        let file = std::fs::File::open(&self.path)
+            .with_context(|| format!("Could not open for reading: {}", self.path.display()))?;
+        Ok(Box::new(file))
+    }
+
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// Sink that streams to a temp file and renames it onto the destination.
+struct LocalSink {
+    path: PathBuf,
+    tmp: PathBuf,
+    file: std::fs::File,
+}
+
+impl Write for LocalSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl BackupSink for LocalSink {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.file.flush().context("Could not flush file")?;
+        std::fs::rename(&self.tmp, &self.path).with_context(|| {
+            format!("Could not move backup into place at {}", self.path.display())
+        })
+    }
+}
+
+impl Drop for LocalSink {
+    fn drop(&mut self) {
+        // If `finish` ran the rename already removed the temp file; otherwise
+        // the write was abandoned, so clean up the partial file.
+        let _ = std::fs::remove_file(&self.tmp);
+    }
+}
+
+/// Sealed backup stored as an object in an S3-compatible bucket. Credentials
+/// are taken from the environment (see `s3::creds::Credentials::default`).
+struct S3Store {
+    bucket: Box<s3::Bucket>,
+    key: String,
+}
+
+impl BackupStore for S3Store {
+    fn put(&self) -> Result<Box<dyn BackupSink>> {
+        Ok(Box::new(S3Sink {
+            bucket: self.bucket.clone(),
+            key: self.key.clone(),
+            buf: Vec::new(),
+        }))
+    }
+
+    fn get(&self) -> Result<Box<dyn Read>> {
+        let response = self
+            .bucket
+            .get_object_blocking(&self.key)
+            .context("Could not download object from S3")?;
+        ensure!(
+            (200..300).contains(&response.status_code()),
+            "S3 download failed with status {}",
+            response.status_code()
+        );
+        Ok(Box::new(Cursor::new(response.bytes().to_vec())))
+    }
+
+    fn describe(&self) -> String {
+        format!("s3://{}/{}", self.bucket.name(), self.key)
+    }
+}
+
+/// Sink that buffers the object in memory and uploads it whole on `finish`,
+/// since the object-store API takes a byte slice rather than a stream.
+struct S3Sink {
+    bucket: Box<s3::Bucket>,
+    key: String,
+    buf: Vec<u8>,
+}
+
+impl Write for S3Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl BackupSink for S3Sink {
+    fn finish(self: Box<Self>) -> Result<()> {
+        let response = self
+            .bucket
+            .put_object_blocking(&self.key, &self.buf)
+            .context("Could not upload object to S3")?;
+        ensure!(
+            (200..300).contains(&response.status_code()),
+            "S3 upload failed with status {}",
+            response.status_code()
+        );
+        Ok(())
+    }
+}
+
 impl Opt {
-    fn get_file(&self) -> Result<Cow<Path>> {
-        self.file.as_ref().map_or_else(
-            || {
-                let mut path =
-                    directories_next::ProjectDirs::from("com", "Snoyman", "BitWarden Backup")
-                        .context("Unable to get project directories")
-                        .map(|pd| pd.config_dir().to_owned())?;
-                std::fs::create_dir_all(&path)
-                    .with_context(|| format!("Could not create directory {}", path.display()))?;
-                path.push("backup.json.enc");
-                Ok(path.into())
-            },
-            |path| Ok(path.as_path().into()),
-        )
+    /// Build the storage backend selected by the command-line flags, falling
+    /// back to a local file.
+    fn get_store(&self, config: &Config) -> Result<Box<dyn BackupStore>> {
+        match &self.s3_bucket {
+            Some(bucket) => {
+                let region = match (&self.s3_region, &self.s3_endpoint) {
+                    (Some(region), Some(endpoint)) => s3::Region::Custom {
+                        region: region.clone(),
+                        endpoint: endpoint.clone(),
+                    },
+                    (Some(region), None) => region
+                        .parse()
+                        .with_context(|| format!("Invalid S3 region: {region}"))?,
+                    (None, _) => bail!("--s3-region is required when using --s3-bucket"),
+                };
+                let credentials = s3::creds::Credentials::default()
+                    .context("Could not load S3 credentials")?;
+                let mut bucket = s3::Bucket::new(bucket, region, credentials)
+                    .context("Could not open S3 bucket")?;
+                if self.s3_endpoint.is_some() {
+                    bucket = bucket.with_path_style();
+                }
+                let key = self
+                    .s3_key
+                    .clone()
+                    .unwrap_or_else(|| "backup.json.enc".to_owned());
+                Ok(Box::new(S3Store { bucket, key }))
+            }
+            None => Ok(Box::new(LocalStore {
+                path: self.get_file(config)?.into_owned(),
+            })),
+        }
+    }
+
+    /// Resolve the local backup path: the `--file` flag wins, then the
+    /// configured path, then the computed default location.
+    fn get_file(&self, config: &Config) -> Result<Cow<Path>> {
+        if let Some(path) = &self.file {
+            return Ok(path.as_path().into());
+        }
+        if let Some(path) = &config.file {
+            return Ok(path.as_path().into());
+        }
+        let mut path = directories_next::ProjectDirs::from("com", "Snoyman", "BitWarden Backup")
+            .context("Unable to get project directories")
+            .map(|pd| pd.config_dir().to_owned())?;
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Could not create directory {}", path.display()))?;
+        path.push("backup.json.enc");
+        Ok(path.into())
     }
 }
 
@@ -65,20 +439,81 @@ fn main() -> Result<()> {
         env_logger::Env::default().default_filter_or(if opt.verbose { "debug" } else { "info" }),
     )
     .init();
-    let filepath = opt.get_file()?;
 
-    log::debug!("File path is {}", filepath.display());
+    let mut config = Config::load(opt.file.as_deref())?;
+
+    // `config` manages the persisted state itself and needs neither a backup
+    // store nor the master password.
+    if let Command::Config { cmd } = &opt.cmd {
+        return match cmd {
+            ConfigCommand::Show {} => {
+                let json = serde_json::to_string_pretty(&config)
+                    .context("Could not serialize config")?;
+                println!("{json}");
+                Ok(())
+            }
+            ConfigCommand::Set { key, value } => {
+                config.set(key, value)?;
+                config.save(opt.file.as_deref())?;
+                Ok(())
+            }
+        };
+    }
+
+    let store = opt.get_store(&config)?;
+
+    log::debug!("Backup location is {}", store.describe());
 
     let password = rpassword::prompt_password("Master password: ")
         .context("Could not read master password")?;
 
+    let ops_limit = opt_ops(&opt.cmd, &config);
+    let mem_limit = opt_mem(&opt.cmd, &config);
+
     match &opt.cmd {
-        Command::Backup { email } => backup(&filepath, email, &password),
-        Command::Restore {} => restore(&filepath, &password),
+        Command::Backup { email, format, .. } => {
+            let email = email
+                .clone()
+                .or_else(|| config.email.clone())
+                .context("No email given; pass --email or set it with `config set email`")?;
+            backup(store.as_ref(), &email, &password, ops_limit, mem_limit, *format)
+        }
+        Command::Restore { to } => restore(store.as_ref(), &password, *to),
+        Command::Verify {} => verify(store.as_ref(), &password),
+        Command::Config { .. } => unreachable!("handled above"),
     }
 }
 
-fn backup(filepath: &Path, email: &str, password: &str) -> Result<()> {
+/// Resolve the KDF operations limit: command-line flag, then config, then
+/// libsodium's interactive default.
+fn opt_ops(cmd: &Command, config: &Config) -> pwhash::OpsLimit {
+    let flag = match cmd {
+        Command::Backup { ops_limit, .. } => *ops_limit,
+        _ => None,
+    };
+    flag.or(config.ops_limit)
+        .map_or(pwhash::OPSLIMIT_INTERACTIVE, |o| pwhash::OpsLimit(o as usize))
+}
+
+/// Resolve the KDF memory limit: command-line flag, then config, then
+/// libsodium's interactive default.
+fn opt_mem(cmd: &Command, config: &Config) -> pwhash::MemLimit {
+    let flag = match cmd {
+        Command::Backup { mem_limit, .. } => *mem_limit,
+        _ => None,
+    };
+    flag.or(config.mem_limit)
+        .map_or(pwhash::MEMLIMIT_INTERACTIVE, |m| pwhash::MemLimit(m as usize))
+}
+
+fn backup(
+    store: &dyn BackupStore,
+    email: &str,
+    password: &str,
+    ops: pwhash::OpsLimit,
+    mem: pwhash::MemLimit,
+    format: ExportFormat,
+) -> Result<()> {
     let login_exit_status = std::process::Command::new("bw")
         .arg("--raw")
         .arg("--nointeraction")
@@ -106,53 +541,379 @@ fn backup(filepath: &Path, email: &str, password: &str) -> Result<()> {
     ensure!(output.status.success(), "bw unlock exited unsuccessfully");
     let session = String::from_utf8(output.stdout).context("Invalid UTF8 encoding in stdout")?;
 
-    let output = std::process::Command::new("bw")
+    let mut child = std::process::Command::new("bw")
         .arg("--raw")
         .arg("--nointeraction")
         .arg("export")
         .arg(password)
         .arg("--format")
-        .arg("json")
+        .arg(format.as_bw_arg())
         .env(SESSIONENV, session)
-        .output()
-        .context("Error running 'bw unlock'")?;
-    log::debug!("bw export output: {:?}", output);
-    ensure!(output.status.success(), "bw export exited unsuccessfully");
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .context("Error running 'bw export'")?;
+    let mut export = child.stdout.take().context("Could not capture 'bw export' stdout")?;
+
+    let mut sink = store.put()?;
+    seal(password, &mut export, &mut *sink, ops, mem)?;
 
-    let sealed = seal(password, &output.stdout)?;
+    let export_status = child.wait().context("Error waiting for 'bw export'")?;
+    log::debug!("bw export status: {:?}", export_status);
+    ensure!(export_status.success(), "bw export exited unsuccessfully");
 
-    let mut file = std::fs::File::create(&filepath).context("Could not open save file")?;
-    file.write_all(&sealed)
-        .context("Could not write output to file")?;
-    file.flush().context("Could not flush file")?;
-    println!("Saved to {}", filepath.display());
+    // Only commit once we know the export succeeded.
+    sink.finish()?;
+    println!("Saved to {}", store.describe());
 
     Ok(())
 }
 
-fn seal(password: &str, data: &[u8]) -> Result<Vec<u8>> {
-    let mut kb = [0; secretbox::KEYBYTES];
+/// Stream `reader` through the secretstream into `writer`, prefixed by the
+/// versioned header carrying the KDF work factor and the stream header.
+fn seal<R: Read, W: Write>(
+    password: &str,
+    reader: &mut R,
+    writer: &mut W,
+    ops: pwhash::OpsLimit,
+    mem: pwhash::MemLimit,
+) -> Result<()> {
     let salt = pwhash::gen_salt();
-    let nonce = secretbox::gen_nonce();
-    pwhash::derive_key_interactive(&mut kb, password.as_bytes(), &salt)
+    let key = derive_key(password, &salt, ops, mem)?;
+    let (mut stream, stream_header) =
+        secretstream::Stream::init_push(&key).ok().context("Could not init stream")?;
+
+    writer.write_all(MAGIC).context("Could not write header")?;
+    writer.write_all(&[VERSION]).context("Could not write header")?;
+    writer
+        .write_all(&(ops.0 as u64).to_le_bytes())
+        .context("Could not write header")?;
+    writer
+        .write_all(&(mem.0 as u64).to_le_bytes())
+        .context("Could not write header")?;
+    writer
+        .write_all(&(CHUNK_SIZE as u64).to_le_bytes())
+        .context("Could not write header")?;
+    writer.write_all(&salt.0).context("Could not write header")?;
+    writer
+        .write_all(&stream_header.0)
+        .context("Could not write header")?;
+
+    // Read one chunk ahead so we know which chunk is last and can tag it as
+    // final, making truncation detectable on decrypt.
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    let mut pending = read_chunk(reader, &mut chunk)?;
+    loop {
+        let mut next = vec![0u8; CHUNK_SIZE];
+        let next_len = read_chunk(reader, &mut next)?;
+        let tag = if next_len == 0 {
+            secretstream::Tag::Final
+        } else {
+            secretstream::Tag::Message
+        };
+        let ciphertext = stream
+            .push(&chunk[..pending], None, tag)
+            .ok()
+            .context("Could not encrypt chunk")?;
+        writer
+            .write_all(&ciphertext)
+            .context("Could not write output to file")?;
+        if matches!(tag, secretstream::Tag::Final) {
+            break;
+        }
+        chunk = next;
+        pending = next_len;
+    }
+    Ok(())
+}
+
+/// Fill `buf` from `reader`, tolerating short reads; returns the number of
+/// bytes read (0 at end of input).
+fn read_chunk<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = reader.read(&mut buf[filled..]).context("Could not read input")?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+fn restore(store: &dyn BackupStore, password: &str, to: Option<RestoreFormat>) -> Result<()> {
+    match to {
+        // Without a conversion, stream the plaintext straight to stdout so
+        // memory stays bounded.
+        None => {
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            decrypt_to(store, password, &mut stdout)?;
+            Ok(())
+        }
+        Some(format) => {
+            let mut decrypted = Vec::new();
+            decrypt_to(store, password, &mut decrypted)?;
+            let converted = convert(&decrypted, format)?;
+            std::io::stdout()
+                .write_all(&converted)
+                .context("Unable to write to stdout")
+        }
+    }
+}
+
+/// Re-encode a BitWarden JSON export into the requested format.
+fn convert(decrypted: &[u8], format: RestoreFormat) -> Result<Vec<u8>> {
+    let export: serde_json::Value =
+        serde_json::from_slice(decrypted).context("Backup is not valid BitWarden JSON")?;
+    match format {
+        RestoreFormat::Json => {
+            serde_json::to_vec_pretty(&export).context("Could not re-encode as JSON")
+        }
+        RestoreFormat::Csv => export_to_csv(&export),
+    }
+}
+
+/// Render the `items` of a BitWarden JSON export as the standard BitWarden CSV
+/// columns, which most password managers can import.
+fn export_to_csv(export: &serde_json::Value) -> Result<Vec<u8>> {
+    let items = export
+        .get("items")
+        .and_then(|i| i.as_array())
+        .context("BitWarden export has no items array")?;
+
+    // Map folder ids to their names for the `folder` column.
+    let mut folders = std::collections::HashMap::new();
+    if let Some(list) = export.get("folders").and_then(|f| f.as_array()) {
+        for folder in list {
+            if let (Some(id), Some(name)) = (
+                folder.get("id").and_then(|v| v.as_str()),
+                folder.get("name").and_then(|v| v.as_str()),
+            ) {
+                folders.insert(id.to_owned(), name.to_owned());
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "folder",
+            "favorite",
+            "type",
+            "name",
+            "notes",
+            "fields",
+            "reprompt",
+            "login_uri",
+            "login_username",
+            "login_password",
+            "login_totp",
+        ])
+        .context("Could not write CSV header")?;
+
+    for item in items {
+        let folder = item
+            .get("folderId")
+            .and_then(|v| v.as_str())
+            .and_then(|id| folders.get(id))
+            .cloned()
+            .unwrap_or_default();
+        let favorite = if item.get("favorite").and_then(|v| v.as_bool()) == Some(true) {
+            "1"
+        } else {
+            ""
+        };
+        let kind = match item.get("type").and_then(|v| v.as_u64()) {
+            Some(1) => "login",
+            Some(2) => "note",
+            Some(3) => "card",
+            Some(4) => "identity",
+            _ => "",
+        };
+        let login = item.get("login");
+        let login_uri = login
+            .and_then(|l| l.get("uris"))
+            .and_then(|u| u.as_array())
+            .and_then(|u| u.first())
+            .and_then(|u| u.get("uri"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        writer
+            .write_record([
+                folder.as_str(),
+                favorite,
+                kind,
+                str_field(item, "name"),
+                str_field(item, "notes"),
+                "",
+                if item.get("reprompt").and_then(|v| v.as_u64()) == Some(1) {
+                    "1"
+                } else {
+                    ""
+                },
+                login_uri,
+                login.map(|l| str_field(l, "username")).unwrap_or_default(),
+                login.map(|l| str_field(l, "password")).unwrap_or_default(),
+                login.map(|l| str_field(l, "totp")).unwrap_or_default(),
+            ])
+            .context("Could not write CSV record")?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| anyhow!("Could not flush CSV output: {e}"))
+}
+
+/// Read a string field from a JSON object, treating missing or null as empty.
+fn str_field<'a>(value: &'a serde_json::Value, key: &str) -> &'a str {
+    value.get(key).and_then(|v| v.as_str()).unwrap_or_default()
+}
+
+/// Decrypt a backup and report a summary of its contents, without ever
+/// emitting the decrypted secrets. The AEAD only lets us tell a decryption
+/// failure (a wrong password and corrupt ciphertext are the same MAC failure)
+/// apart from a successful decrypt that isn't valid BitWarden JSON, so those
+/// are the two distinct error contexts it reports.
+fn verify(store: &dyn BackupStore, password: &str) -> Result<()> {
+    let mut decrypted = Vec::new();
+    let version = decrypt_to(store, password, &mut decrypted)
+        .context("Backup did not decrypt — wrong password or corrupt ciphertext")?;
+    let byte_length = decrypted.len();
+
+    let export: serde_json::Value = serde_json::from_slice(&decrypted)
+        .context("Backup decrypted but is not valid BitWarden JSON")?;
+    let items = export
+        .get("items")
+        .and_then(|v| v.as_array())
+        .map_or(0, |a| a.len());
+    let folders = export
+        .get("folders")
+        .and_then(|v| v.as_array())
+        .map_or(0, |a| a.len());
+
+    println!("Backup OK");
+    println!("  format version: {version}");
+    println!("  items:          {items}");
+    println!("  folders:        {folders}");
+    println!("  plaintext size: {byte_length} bytes");
+    Ok(())
+}
+
+/// Decrypt a stored backup into `writer`, transparently handling the versioned
+/// stream format and the legacy layouts. Returns the detected on-disk format
+/// version (0 for the unversioned legacy layout).
+fn decrypt_to<W: Write>(store: &dyn BackupStore, password: &str, writer: &mut W) -> Result<u8> {
+    let mut reader = BufReader::new(store.get()?);
+
+    let mut magic = [0u8; 4];
+    let read = read_chunk(&mut reader, &mut magic)?;
+    if read == MAGIC.len() && &magic == MAGIC {
+        let mut version = [0u8; 1];
+        ensure!(
+            read_chunk(&mut reader, &mut version)? == 1,
+            "Truncated backup header"
+        );
+        match version[0] {
+            VERSION => unseal_stream(password, &mut reader, writer)?,
+            1 => {
+                // v1 is a single secretbox over the whole payload; buffer it.
+                let mut rest = Vec::new();
+                reader.read_to_end(&mut rest).context("Could not read file")?;
+                let decrypted = unseal_v1(password, &rest)?;
+                writer.write_all(&decrypted).context("Unable to write to stdout")?;
+            }
+            other => bail!("Unsupported backup version {}", other),
+        }
+        Ok(version[0])
+    } else {
+        // Legacy v0 layout with no magic: reassemble the prefix we consumed.
+        let mut buf = magic[..read].to_vec();
+        reader.read_to_end(&mut buf).context("Could not read file")?;
+        let decrypted = unseal_v0(password, &buf)?;
+        writer.write_all(&decrypted).context("Unable to write to stdout")?;
+        Ok(0)
+    }
+}
+
+/// Decrypt a v2 stream chunk-by-chunk, keeping memory bounded regardless of
+/// vault size. `reader` is positioned just past the magic and version bytes.
+fn unseal_stream<R: Read, W: Write>(password: &str, reader: &mut R, writer: &mut W) -> Result<()> {
+    let mut eight = [0u8; 8];
+    ensure!(read_chunk(reader, &mut eight)? == 8, "Truncated backup header");
+    let ops = u64::from_le_bytes(eight);
+    ensure!(read_chunk(reader, &mut eight)? == 8, "Truncated backup header");
+    let mem = u64::from_le_bytes(eight);
+    ensure!(read_chunk(reader, &mut eight)? == 8, "Truncated backup header");
+    let chunk_size = u64::from_le_bytes(eight) as usize;
+
+    let mut salt_bytes = [0u8; pwhash::SALTBYTES];
+    ensure!(
+        read_chunk(reader, &mut salt_bytes)? == pwhash::SALTBYTES,
+        "Truncated backup header"
+    );
+    let salt = pwhash::Salt(salt_bytes);
+    let mut header_bytes = [0u8; secretstream::HEADERBYTES];
+    ensure!(
+        read_chunk(reader, &mut header_bytes)? == secretstream::HEADERBYTES,
+        "Truncated backup header"
+    );
+    let stream_header =
+        secretstream::Header::from_slice(&header_bytes).context("Invalid stream header")?;
+
+    let key = derive_key(
+        password,
+        &salt,
+        pwhash::OpsLimit(ops as usize),
+        pwhash::MemLimit(mem as usize),
+    )?;
+    let mut stream = secretstream::Stream::init_pull(&stream_header, &key)
         .ok()
-        .context("Could not derive key")?;
-    let key = secretbox::Key(kb);
-    let encrypted = secretbox::seal(data, &nonce, &key);
+        .context("Could not init stream")?;
 
-    let mut result = Vec::new();
-    result.extend_from_slice(&salt.0);
-    result.extend_from_slice(&nonce.0);
-    result.extend_from_slice(&encrypted);
-    Ok(result)
+    // Each on-disk chunk is one plaintext chunk plus the secretstream tag.
+    let mut buf = vec![0u8; chunk_size + secretstream::ABYTES];
+    while stream.is_not_finalized() {
+        let n = read_chunk(reader, &mut buf)?;
+        ensure!(n > 0, "Truncated backup: stream ended before final chunk");
+        let (plaintext, _tag) = stream
+            .pull(&buf[..n], None)
+            .ok()
+            .context("Unable to decrypt")?;
+        writer.write_all(&plaintext).context("Unable to write to stdout")?;
+    }
+    Ok(())
 }
 
-fn restore(filepath: &Path, password: &str) -> Result<()> {
-    let mut file = std::fs::File::open(filepath)
-        .with_context(|| format!("Could not open for reading: {}", filepath.display()))?;
-    let mut buf = Vec::new();
-    file.read_to_end(&mut buf).context("Could not read file")?;
+/// Decrypt the legacy v1 single-secretbox layout (magic and version already
+/// consumed by the caller).
+fn unseal_v1(password: &str, buf: &[u8]) -> Result<Vec<u8>> {
+    let body_len = V1_HEADER_LEN - MAGIC.len() - 1;
+    ensure!(buf.len() > body_len, "Insufficient bytes in file");
+    let ops = u64::from_le_bytes(buf[0..8].try_into().unwrap());
+    let mem = u64::from_le_bytes(buf[8..16].try_into().unwrap());
+    let salt = pwhash::Salt::from_slice(&buf[16..16 + pwhash::SALTBYTES]).expect("Invalid salt size");
+    let nonce_start = 16 + pwhash::SALTBYTES;
+    let nonce = secretbox::Nonce::from_slice(&buf[nonce_start..nonce_start + secretbox::NONCEBYTES])
+        .expect("Invalid nonce size");
+    let mut kb = [0; secretbox::KEYBYTES];
+    pwhash::derive_key(
+        &mut kb,
+        password.as_bytes(),
+        &salt,
+        pwhash::OpsLimit(ops as usize),
+        pwhash::MemLimit(mem as usize),
+    )
+    .ok()
+    .context("Could not derive key")?;
+    let key = secretbox::Key(kb);
+    secretbox::open(&buf[nonce_start + secretbox::NONCEBYTES..], &nonce, &key)
+        .ok()
+        .context("Unable to decrypt")
+}
 
+/// Decrypt the original unversioned `salt | nonce | ciphertext` layout derived
+/// with libsodium's interactive defaults.
+fn unseal_v0(password: &str, buf: &[u8]) -> Result<Vec<u8>> {
     ensure!(buf.len() > 56, "Insufficient bytes in file");
     let salt = pwhash::Salt::from_slice(&buf[0..32]).expect("Invalid salt size");
     let nonce = secretbox::Nonce::from_slice(&buf[32..56]).expect("Invalid nonce size");
@@ -161,13 +922,185 @@ fn restore(filepath: &Path, password: &str) -> Result<()> {
         .ok()
         .context("Could not derive key")?;
     let key = secretbox::Key(kb);
-    let decrypted = secretbox::open(&buf[56..], &nonce, &key)
+    secretbox::open(&buf[56..], &nonce, &key)
         .ok()
-        .context("Unable to decrypt")?;
+        .context("Unable to decrypt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const PW: &str = "correct horse battery staple";
+
+    /// An in-memory backup store so the crypto paths can be exercised without
+    /// touching the filesystem, the network, or the `bw` binary.
+    #[derive(Clone, Default)]
+    struct MemStore(Arc<Mutex<Vec<u8>>>);
+
+    struct MemSink {
+        shared: Arc<Mutex<Vec<u8>>>,
+        buf: Vec<u8>,
+    }
+
+    impl Write for MemSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl BackupSink for MemSink {
+        fn finish(self: Box<Self>) -> Result<()> {
+            *self.shared.lock().unwrap() = self.buf;
+            Ok(())
+        }
+    }
+
+    impl BackupStore for MemStore {
+        fn put(&self) -> Result<Box<dyn BackupSink>> {
+            Ok(Box::new(MemSink {
+                shared: self.0.clone(),
+                buf: Vec::new(),
+            }))
+        }
+
+        fn get(&self) -> Result<Box<dyn Read>> {
+            Ok(Box::new(Cursor::new(self.0.lock().unwrap().clone())))
+        }
+
+        fn describe(&self) -> String {
+            "memory".to_owned()
+        }
+    }
+
+    /// Cheap-but-valid work factor; the tests only care about round-tripping.
+    fn limits() -> (pwhash::OpsLimit, pwhash::MemLimit) {
+        (pwhash::OPSLIMIT_INTERACTIVE, pwhash::MEMLIMIT_INTERACTIVE)
+    }
 
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
-    stdout
-        .write_all(&decrypted)
-        .context("Unable to write to stdout")
+    fn store_with(bytes: Vec<u8>) -> MemStore {
+        MemStore(Arc::new(Mutex::new(bytes)))
+    }
+
+    fn roundtrip(payload: &[u8]) {
+        let (ops, mem) = limits();
+        let store = MemStore::default();
+        let mut sink = store.put().unwrap();
+        seal(PW, &mut &payload[..], &mut *sink, ops, mem).unwrap();
+        sink.finish().unwrap();
+
+        let mut out = Vec::new();
+        let version = decrypt_to(&store, PW, &mut out).unwrap();
+        assert_eq!(version, VERSION);
+        assert_eq!(out, payload);
+    }
+
+    #[test]
+    fn roundtrip_empty() {
+        roundtrip(b"");
+    }
+
+    #[test]
+    fn roundtrip_single_chunk() {
+        roundtrip(b"{\"items\":[]}");
+    }
+
+    #[test]
+    fn roundtrip_multi_chunk() {
+        let payload: Vec<u8> = (0..CHUNK_SIZE * 2 + 123).map(|i| i as u8).collect();
+        roundtrip(&payload);
+    }
+
+    #[test]
+    fn wrong_password_fails() {
+        let (ops, mem) = limits();
+        let store = MemStore::default();
+        let mut sink = store.put().unwrap();
+        seal(PW, &mut &b"secret"[..], &mut *sink, ops, mem).unwrap();
+        sink.finish().unwrap();
+
+        let mut out = Vec::new();
+        assert!(decrypt_to(&store, "wrong", &mut out).is_err());
+    }
+
+    #[test]
+    fn decrypts_legacy_v0() {
+        let payload = b"{\"items\":[],\"folders\":[]}";
+        let salt = pwhash::gen_salt();
+        let nonce = secretbox::gen_nonce();
+        let mut kb = [0u8; secretbox::KEYBYTES];
+        pwhash::derive_key_interactive(&mut kb, PW.as_bytes(), &salt).unwrap();
+        let key = secretbox::Key(kb);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&salt.0);
+        buf.extend_from_slice(&nonce.0);
+        buf.extend_from_slice(&secretbox::seal(payload, &nonce, &key));
+
+        let mut out = Vec::new();
+        let version = decrypt_to(&store_with(buf), PW, &mut out).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(out, payload);
+    }
+
+    #[test]
+    fn decrypts_legacy_v1() {
+        let payload = b"{\"items\":[],\"folders\":[]}";
+        let (ops, mem) = limits();
+        let salt = pwhash::gen_salt();
+        let nonce = secretbox::gen_nonce();
+        let mut kb = [0u8; secretbox::KEYBYTES];
+        pwhash::derive_key(&mut kb, PW.as_bytes(), &salt, ops, mem).unwrap();
+        let key = secretbox::Key(kb);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(1);
+        buf.extend_from_slice(&(ops.0 as u64).to_le_bytes());
+        buf.extend_from_slice(&(mem.0 as u64).to_le_bytes());
+        buf.extend_from_slice(&salt.0);
+        buf.extend_from_slice(&nonce.0);
+        buf.extend_from_slice(&secretbox::seal(payload, &nonce, &key));
+
+        let mut out = Vec::new();
+        let version = decrypt_to(&store_with(buf), PW, &mut out).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(out, payload);
+    }
+
+    #[test]
+    fn converts_export_to_csv() {
+        let export = serde_json::json!({
+            "folders": [{"id": "f1", "name": "Work"}],
+            "items": [{
+                "type": 1,
+                "name": "Example",
+                "folderId": "f1",
+                "favorite": true,
+                "login": {
+                    "username": "alice",
+                    "password": "hunter2",
+                    "uris": [{"uri": "https://example.com"}]
+                }
+            }]
+        });
+        let csv = export_to_csv(&export).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp"
+        );
+        let row = lines.next().unwrap();
+        assert_eq!(
+            row,
+            "Work,1,login,Example,,,,https://example.com,alice,hunter2,"
+        );
+    }
 }